@@ -1,62 +1,245 @@
 use anyhow::{Context, Result};
 use base64::{Engine as _, engine::general_purpose};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
 use chrono;
 use clap::Parser;
+use ethers::prelude::*;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 use tempfile::TempDir;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::{self, Certificate, PrivateKey, ServerConfig};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     #[arg(short, long, default_value = "8080")]
     port: u16,
+
+    /// Serve the line-based TCP protocol over TLS instead of plaintext (requires
+    /// --tls-cert and --tls-key). The HTTP API (--http) is served in plaintext.
+    #[arg(long)]
+    tls: bool,
+
+    /// Path to the PEM-encoded TLS certificate chain.
+    #[arg(long)]
+    tls_cert: Option<String>,
+
+    /// Path to the PEM-encoded TLS private key.
+    #[arg(long)]
+    tls_key: Option<String>,
+
+    /// JSON-RPC endpoint of the EVM chain to submit proofs to.
+    #[arg(long)]
+    eth_rpc: Option<String>,
+
+    /// Address of the deployed Solidity verifier contract (0x...).
+    #[arg(long)]
+    verifier_address: Option<String>,
+
+    /// Private key used to sign the on-chain verification transaction.
+    #[arg(long)]
+    eth_private_key: Option<String>,
+
+    /// Directory holding the named circuits served by this instance.
+    #[arg(long)]
+    circuits_dir: Option<String>,
+
+    /// Circuit the line-based TCP protocol proves against by default.
+    #[arg(long, default_value = "insurance")]
+    circuit: String,
+
+    /// Serve a JSON HTTP API instead of the line-based TCP protocol.
+    #[arg(long)]
+    http: bool,
+
+    /// 32-byte AEAD key as hex; when set, saved proofs are encrypted at rest.
+    #[arg(long)]
+    encrypt_key: Option<String>,
+
+    /// Decrypt a `proof_*.enc` file with --encrypt-key and print its JSON.
+    #[arg(long)]
+    decrypt: Option<String>,
 }
 
+// ABI of the Solidity verifier exported from the circuit via `bb contract`.
+// Only the `verify` entry point is needed to check and record a proof on-chain.
+abigen!(
+    InsuranceVerifier,
+    r#"[
+        function verify(bytes proof, uint256[] publicInputs) returns (bool)
+    ]"#,
+);
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ProofRequest {
-    age: u32,
-    bmi_multiplied: u32, // BMI * 10 to avoid decimals
+    /// Name of the circuit in the registry to prove against.
+    circuit: String,
+    /// Private inputs keyed by the names declared in the circuit schema.
+    inputs: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 struct ProofResponse {
     proof: String,
     verification_key: String,
     public_inputs: PublicInputs,
     success: bool,
     message: String,
+    /// Hash of the transaction that recorded the proof on-chain, if submitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tx_hash: Option<String>,
+    /// Result reported by the on-chain verifier contract, if submitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_chain_verified: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PublicInputs {
-    min_age: u32,
-    max_age: u32,
-    min_bmi: u32,
-    max_bmi: u32,
+/// Public inputs (policy bounds) reported alongside a proof. These are fixed by
+/// the circuit's policy config rather than supplied by the client. Order is
+/// significant — it must match the circuit's public-input signature so the
+/// on-chain verifier receives the `uint256[]` in the layout it expects — so the
+/// pairs are kept in a `Vec` rather than a map. Serialized as a JSON object that
+/// preserves that order. No `Deserialize` is provided on purpose: collecting
+/// back into a `serde_json::Map` would silently re-sort the bounds and defeat
+/// the ordering invariant, so the type is serialize-only.
+#[derive(Debug, Clone, Default)]
+struct PublicInputs(Vec<(String, serde_json::Value)>);
+
+impl Serialize for PublicInputs {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A single public input (policy bound) in the circuit's declared order.
+#[derive(Debug, Clone, Deserialize)]
+struct PublicInput {
+    name: String,
+    value: toml::Value,
+}
+
+/// Policy + schema for a single circuit, loaded from its `policy.toml`.
+#[derive(Debug, Clone, Deserialize)]
+struct CircuitConfig {
+    /// Compiled artifact name under `target/` (without extension).
+    package: String,
+    /// Private-input names the client must supply, in `Prover.toml` order.
+    #[serde(default)]
+    private_inputs: Vec<String>,
+    /// Public-input bounds written into `Prover.toml`, in the circuit's
+    /// public-input signature order (an array of tables preserves that order).
+    #[serde(default)]
+    public_inputs: Vec<PublicInput>,
+}
+
+/// A circuit on disk: its source directory plus its parsed policy config.
+#[derive(Debug, Clone)]
+struct Circuit {
+    name: String,
+    path: PathBuf,
+    config: CircuitConfig,
+}
+
+/// Named collection of circuits loaded from a directory, each in its own
+/// subdirectory with a `Nargo.toml`, `src/main.nr`, and `policy.toml`.
+struct CircuitRegistry {
+    circuits: HashMap<String, Circuit>,
+}
+
+impl CircuitRegistry {
+    /// Load every circuit subdirectory under `dir`. A subdirectory without a
+    /// `policy.toml` is skipped so stray files don't break startup.
+    fn load(dir: &Path) -> Result<Self> {
+        let mut circuits = HashMap::new();
+        for entry in fs::read_dir(dir)
+            .with_context(|| format!("Failed to read circuits directory: {}", dir.display()))?
+        {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let policy_path = path.join("policy.toml");
+            if !policy_path.exists() {
+                continue;
+            }
+            let policy = fs::read_to_string(&policy_path)
+                .with_context(|| format!("Failed to read {}", policy_path.display()))?;
+            let config: CircuitConfig = toml::from_str(&policy)
+                .with_context(|| format!("Failed to parse {}", policy_path.display()))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            circuits.insert(name.clone(), Circuit { name, path, config });
+        }
+        Ok(Self { circuits })
+    }
+
+    fn get(&self, name: &str) -> Option<Circuit> {
+        self.circuits.get(name).cloned()
+    }
+}
+
+/// Render a JSON scalar as the text placed between the quotes of a
+/// `Prover.toml` assignment (strings unquoted, numbers/bools as text).
+fn json_scalar(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Same as [`json_scalar`] but for TOML policy values.
+fn toml_scalar(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(i) => i.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Project a policy's public inputs into the JSON shape carried by
+/// [`PublicInputs`] in proof responses, preserving the circuit's declared order.
+fn policy_public_inputs(public_inputs: &[PublicInput]) -> PublicInputs {
+    let pairs = public_inputs
+        .iter()
+        .map(|pi| {
+            let json = match &pi.value {
+                toml::Value::Integer(i) => serde_json::Value::from(*i),
+                toml::Value::String(s) => serde_json::Value::from(s.clone()),
+                toml::Value::Boolean(b) => serde_json::Value::from(*b),
+                other => serde_json::Value::from(other.to_string()),
+            };
+            (pi.name.clone(), json)
+        })
+        .collect();
+    PublicInputs(pairs)
 }
 
 struct NoirProver {
-    circuit_path: String,
+    circuit: Circuit,
 }
 
 impl NoirProver {
-    fn new() -> Self {
-        // Check if we're running in Docker (where circuit is at /app/noir-circuit)
-        // or locally (where circuit is at ../noir-circuit)
-        let circuit_path = if std::path::Path::new("/app/noir-circuit").exists() {
-            "/app/noir-circuit".to_string()
-        } else {
-            "../noir-circuit".to_string()
-        };
-        
-        Self {
-            circuit_path,
-        }
+    fn new(circuit: Circuit) -> Self {
+        Self { circuit }
     }
 
     async fn generate_proof(&self, request: ProofRequest) -> Result<ProofResponse> {
@@ -67,16 +250,23 @@ impl NoirProver {
         // Copy the circuit to temporary directory
         self.copy_circuit_to_temp(temp_path)?;
 
-        // Update Prover.toml with the input values
-        let prover_toml_content = format!(
-            r#"age = "{}"
-bmi = "{}"
-min_age = "10"
-max_age = "25"
-min_bmi = "185"
-max_bmi = "249""#,
-            request.age, request.bmi_multiplied
-        );
+        let config = &self.circuit.config;
+        let public_inputs = policy_public_inputs(&config.public_inputs);
+
+        // Build Prover.toml dynamically from the declared input schema (client
+        // values) and the policy config (public bounds) rather than a fixed
+        // template, so a new policy is a config change, not a source edit.
+        let mut prover_lines = Vec::new();
+        for name in &config.private_inputs {
+            let value = request.inputs.get(name).with_context(|| {
+                format!("Missing required input '{}' for circuit '{}'", name, self.circuit.name)
+            })?;
+            prover_lines.push(format!("{} = \"{}\"", name, json_scalar(value)));
+        }
+        for pi in &config.public_inputs {
+            prover_lines.push(format!("{} = \"{}\"", pi.name, toml_scalar(&pi.value)));
+        }
+        let prover_toml_content = prover_lines.join("\n");
 
         let prover_path = temp_path.join("Prover.toml");
         fs::write(&prover_path, prover_toml_content)?;
@@ -92,13 +282,10 @@ max_bmi = "249""#,
             return Ok(ProofResponse {
                 proof: String::new(),
                 verification_key: String::new(),
-                public_inputs: PublicInputs {
-                    min_age: 10,
-                    max_age: 25,
-                    min_bmi: 185,
-                    max_bmi: 249,
-                },
+                public_inputs: public_inputs.clone(),
                 success: false,
+                tx_hash: None,
+                on_chain_verified: None,
                 message: format!(
                     "Circuit compilation failed: {}",
                     String::from_utf8_lossy(&compile_output.stderr)
@@ -117,13 +304,10 @@ max_bmi = "249""#,
             return Ok(ProofResponse {
                 proof: String::new(),
                 verification_key: String::new(),
-                public_inputs: PublicInputs {
-                    min_age: 10,
-                    max_age: 25,
-                    min_bmi: 185,
-                    max_bmi: 249,
-                },
+                public_inputs: public_inputs.clone(),
                 success: false,
+                tx_hash: None,
+                on_chain_verified: None,
                 message: format!(
                     "Circuit execution failed. Likely the inputs don't satisfy the constraints: {}",
                     String::from_utf8_lossy(&execute_output.stderr)
@@ -131,32 +315,141 @@ max_bmi = "249""#,
             });
         }
 
-        // Read the generated witness file
-        let witness_path = temp_path.join("target/insurance_verifier.gz");
-        let witness_bytes = fs::read(&witness_path).context("Failed to read witness file")?;
-        let proof = general_purpose::STANDARD.encode(&witness_bytes);
+        // Nargo only produces a witness; the actual zk-SNARK proof and
+        // verification key come from the Barretenberg backend (`bb`). Generate a
+        // real proof over the compiled circuit and its witness so the output can
+        // be verified by anyone, not just stamped as a placeholder.
+        let circuit_json = temp_path.join(format!("target/{}.json", config.package));
+        let witness_path = temp_path.join(format!("target/{}.gz", config.package));
+        let proof_path = temp_path.join("target/proof");
+        let vk_path = temp_path.join("target/vk");
 
-        // Note: In Nargo 1.0.0, verification key generation is handled differently
-        // and typically requires a separate backend like Barretenberg
-        let verification_key = format!("witness_verification_placeholder_{}", chrono::Utc::now().timestamp());
+        let prove_output = Command::new("bb")
+            .arg("prove")
+            .arg("-b")
+            .arg(&circuit_json)
+            .arg("-w")
+            .arg(&witness_path)
+            .arg("-o")
+            .arg(&proof_path)
+            .current_dir(&temp_path)
+            .output()
+            .context(
+                "Failed to run `bb prove`. Is the Barretenberg backend (`bb`) installed and on PATH?",
+            )?;
+
+        if !prove_output.status.success() {
+            return Ok(ProofResponse {
+                proof: String::new(),
+                verification_key: String::new(),
+                public_inputs: public_inputs.clone(),
+                success: false,
+                tx_hash: None,
+                on_chain_verified: None,
+                message: format!(
+                    "Proof generation failed: {}",
+                    String::from_utf8_lossy(&prove_output.stderr)
+                ),
+            });
+        }
+
+        let vk_output = Command::new("bb")
+            .arg("write_vk")
+            .arg("-b")
+            .arg(&circuit_json)
+            .arg("-o")
+            .arg(&vk_path)
+            .current_dir(&temp_path)
+            .output()
+            .context(
+                "Failed to run `bb write_vk`. Is the Barretenberg backend (`bb`) installed and on PATH?",
+            )?;
+
+        if !vk_output.status.success() {
+            return Ok(ProofResponse {
+                proof: String::new(),
+                verification_key: String::new(),
+                public_inputs: public_inputs.clone(),
+                success: false,
+                tx_hash: None,
+                on_chain_verified: None,
+                message: format!(
+                    "Verification key generation failed: {}",
+                    String::from_utf8_lossy(&vk_output.stderr)
+                ),
+            });
+        }
+
+        // Export a Solidity verifier for the circuit. Best-effort: a missing
+        // Solidity target shouldn't fail an otherwise valid off-chain proof.
+        // `Verifier.sol` is written into this temporary directory and is removed
+        // with it; it is not returned or kept.
+        let contract_path = temp_path.join("Verifier.sol");
+        let _ = Command::new("bb")
+            .arg("contract")
+            .arg("-k")
+            .arg(&vk_path)
+            .arg("-o")
+            .arg(&contract_path)
+            .current_dir(&temp_path)
+            .output();
+
+        // Return the proof bytes (not the witness) so a downstream verifier can
+        // trust the output, alongside the verification key it needs.
+        let proof_bytes = fs::read(&proof_path).context("Failed to read generated proof file")?;
+        let vk_bytes = fs::read(&vk_path).context("Failed to read verification key file")?;
+        let proof = general_purpose::STANDARD.encode(&proof_bytes);
+        let verification_key = general_purpose::STANDARD.encode(&vk_bytes);
 
         Ok(ProofResponse {
             proof,
             verification_key,
-            public_inputs: PublicInputs {
-                min_age: 10,
-                max_age: 25,
-                min_bmi: 185,
-                max_bmi: 249,
-            },
+            public_inputs,
             success: true,
+            tx_hash: None,
+            on_chain_verified: None,
             message: "Proof generated successfully! The user is eligible for insurance discount.".to_string(),
         })
     }
 
+    /// Verify a previously generated proof against its verification key using the
+    /// Barretenberg backend. The base64-encoded `proof` and `verification_key`
+    /// are written back out to a temporary directory and checked with
+    /// `bb verify`, returning whether the proof is valid.
+    async fn verify_proof(proof: &str, verification_key: &str) -> Result<bool> {
+        let temp_dir = TempDir::new()?;
+        let temp_path = temp_dir.path();
+
+        let proof_bytes = general_purpose::STANDARD
+            .decode(proof)
+            .context("Proof is not valid base64")?;
+        let vk_bytes = general_purpose::STANDARD
+            .decode(verification_key)
+            .context("Verification key is not valid base64")?;
+
+        let proof_path = temp_path.join("proof");
+        let vk_path = temp_path.join("vk");
+        fs::write(&proof_path, proof_bytes)?;
+        fs::write(&vk_path, vk_bytes)?;
+
+        let verify_output = Command::new("bb")
+            .arg("verify")
+            .arg("-k")
+            .arg(&vk_path)
+            .arg("-p")
+            .arg(&proof_path)
+            .current_dir(&temp_path)
+            .output()
+            .context(
+                "Failed to run `bb verify`. Is the Barretenberg backend (`bb`) installed and on PATH?",
+            )?;
+
+        Ok(verify_output.status.success())
+    }
+
     fn copy_circuit_to_temp(&self, temp_path: &Path) -> Result<()> {
         // Copy Nargo.toml
-        let source_nargo = Path::new(&self.circuit_path).join("Nargo.toml");
+        let source_nargo = self.circuit.path.join("Nargo.toml");
         let dest_nargo = temp_path.join("Nargo.toml");
         fs::copy(source_nargo, dest_nargo)?;
 
@@ -164,7 +457,7 @@ max_bmi = "249""#,
         let src_dir = temp_path.join("src");
         fs::create_dir_all(&src_dir)?;
         
-        let source_main = Path::new(&self.circuit_path).join("src/main.nr");
+        let source_main = self.circuit.path.join("src/main.nr");
         let dest_main = src_dir.join("main.nr");
         fs::copy(source_main, dest_main)?;
 
@@ -172,43 +465,197 @@ max_bmi = "249""#,
     }
 }
 
-async fn handle_client(mut stream: TcpStream) -> Result<()> {
-    let (reader, mut writer) = stream.split();
-    let mut reader = BufReader::new(reader);
-    let mut line = String::new();
+/// Configuration for recording proofs on an EVM chain.
+#[derive(Debug, Clone)]
+struct EthConfig {
+    rpc: String,
+    verifier_address: String,
+    private_key: String,
+}
 
-    let prover = NoirProver::new();
+/// Submit a generated proof to the on-chain Solidity verifier and record the
+/// result. The base64 `proof` is ABI-encoded as `bytes` and the policy bounds
+/// are passed as the `uint256[]` public inputs, matching the
+/// `verify(bytes,uint256[])` signature exported by `bb contract`. Recording the
+/// result is a state-changing transaction, so it is signed by the configured
+/// wallet via `SignerMiddleware` rather than relying on a node-managed account.
+/// Returns the transaction hash and the boolean the contract reports.
+async fn submit_on_chain(
+    eth: &EthConfig,
+    proof: &str,
+    public_inputs: &PublicInputs,
+) -> Result<(String, bool)> {
+    let provider =
+        Provider::<Http>::try_from(eth.rpc.as_str()).context("Failed to connect to the Ethereum RPC")?;
+    let chain_id = provider
+        .get_chainid()
+        .await
+        .context("Failed to fetch chain id from the Ethereum RPC")?
+        .as_u64();
+    let wallet = eth
+        .private_key
+        .parse::<LocalWallet>()
+        .context("Invalid Ethereum private key")?
+        .with_chain_id(chain_id);
+    let client = Arc::new(SignerMiddleware::new(provider, wallet));
+
+    let address: Address = eth
+        .verifier_address
+        .parse()
+        .context("Invalid verifier contract address")?;
+    let contract = InsuranceVerifier::new(address, client);
+
+    let proof_bytes = general_purpose::STANDARD
+        .decode(proof)
+        .context("Proof is not valid base64")?;
+    let proof = Bytes::from(proof_bytes);
+
+    // Encode every policy bound as a uint256 in the circuit's declared
+    // public-input order so the verifier sees the layout it expects.
+    let mut inputs = Vec::with_capacity(public_inputs.0.len());
+    for (name, value) in &public_inputs.0 {
+        let n = value
+            .as_u64()
+            .with_context(|| format!("Public input '{}' is not an unsigned integer", name))?;
+        inputs.push(U256::from(n));
+    }
+
+    // Read the verifier's verdict, then record the call on-chain.
+    let verified = contract
+        .verify(proof.clone(), inputs.clone())
+        .call()
+        .await
+        .context("On-chain verify() call failed")?;
+
+    let pending = contract
+        .verify(proof, inputs)
+        .send()
+        .await
+        .context("Failed to submit verification transaction")?;
+    let tx_hash = format!("{:#x}", pending.tx_hash());
+
+    Ok((tx_hash, verified))
+}
+
+/// Capabilities a client and server agree on during the opening handshake.
+#[derive(Debug, Clone, Default)]
+struct Capabilities {
+    /// Deliver the proof payload as zstd-compressed, base64-encoded bytes.
+    zstd: bool,
+    /// Keep the connection open to serve multiple proof requests.
+    keepalive: bool,
+}
+
+impl Capabilities {
+    /// Intersect the client's offered capability tokens with what the server
+    /// supports (currently `zstd` and `keepalive`).
+    fn negotiate<'a>(offered: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut caps = Capabilities::default();
+        for token in offered {
+            match token {
+                "zstd" => caps.zstd = true,
+                "keepalive" => caps.keepalive = true,
+                _ => {}
+            }
+        }
+        caps
+    }
+}
+
+/// How long (seconds) a session remains resumable after its last use.
+const SESSION_TTL_SECS: i64 = 3600;
+
+/// A resumable session: the circuit it is bound to, its negotiated caps, and the
+/// Unix timestamp it was last touched (used to bound its lifetime).
+#[derive(Clone)]
+struct Session {
+    circuit: Circuit,
+    caps: Capabilities,
+    last_seen: i64,
+}
+
+/// Sessions shared across connections so a reconnecting client can resume.
+type SessionStore = Arc<Mutex<HashMap<String, Session>>>;
+
+/// Drop every session whose TTL has elapsed. Called on each new connection,
+/// before its handshake line is acted on, so memory and the set of resumable
+/// IDs stay bounded.
+async fn sweep_sessions(sessions: &SessionStore, now: i64) {
+    sessions
+        .lock()
+        .await
+        .retain(|_, session| now - session.last_seen < SESSION_TTL_SECS);
+}
+
+/// Generate a random hex session identifier.
+fn new_session_id() -> String {
+    let mut buf = [0u8; 16];
+    OsRng.fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Run one proof request/response exchange over the connection, honouring the
+/// negotiated capabilities (zstd payload compression) and the on-chain /
+/// at-rest-encryption options.
+async fn run_proof_exchange<W>(
+    writer: &mut W,
+    reader: &mut BufReader<tokio::io::ReadHalf<impl AsyncRead + Unpin>>,
+    prover: &NoirProver,
+    circuit_name: &str,
+    caps: &Capabilities,
+    eth: &Option<EthConfig>,
+    encrypt_key: &Option<[u8; 32]>,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut line = String::new();
 
-    // Send welcome message
-    writer.write_all(b"ZK Insurance Verifier Server\n").await?;
-    writer.write_all(b"============================\n").await?;
     writer.write_all(b"Enter age (10-25): ").await?;
     writer.flush().await?;
-
-    // Read age
-    line.clear();
     reader.read_line(&mut line).await?;
     let age: u32 = line.trim().parse().context("Invalid age input")?;
 
-    // Ask for BMI
     writer.write_all(b"Enter BMI multiplied by 10 (185-249): ").await?;
     writer.flush().await?;
-
-    // Read BMI
     line.clear();
     reader.read_line(&mut line).await?;
     let bmi_multiplied: u32 = line.trim().parse().context("Invalid BMI input")?;
 
+    // The line protocol maps its two prompts onto the insurance circuit's
+    // private inputs; richer circuits are reachable via the JSON transports.
+    let mut inputs = HashMap::new();
+    inputs.insert("age".to_string(), serde_json::Value::from(age));
+    inputs.insert("bmi".to_string(), serde_json::Value::from(bmi_multiplied));
     let request = ProofRequest {
-        age,
-        bmi_multiplied,
+        circuit: circuit_name.to_string(),
+        inputs,
     };
 
     writer.write_all(b"Generating proof...\n").await?;
     writer.flush().await?;
 
     match prover.generate_proof(request).await {
-        Ok(response) => {
+        Ok(mut response) => {
+            // If an on-chain verifier is configured, submit the fresh proof and
+            // fold the transaction hash and verdict into the response.
+            if response.success {
+                if let Some(eth) = eth {
+                    writer.write_all(b"Submitting proof on-chain...\n").await?;
+                    writer.flush().await?;
+                    match submit_on_chain(eth, &response.proof, &response.public_inputs).await {
+                        Ok((tx_hash, verified)) => {
+                            response.tx_hash = Some(tx_hash);
+                            response.on_chain_verified = Some(verified);
+                        }
+                        Err(e) => {
+                            let msg = format!("On-chain submission failed: {}\n", e);
+                            writer.write_all(msg.as_bytes()).await?;
+                        }
+                    }
+                }
+            }
+
             let response_text = format!(
                 "\n=== PROOF RESPONSE ===\nSuccess: {}\nMessage: {}\n",
                 response.success, response.message
@@ -222,23 +669,44 @@ async fn handle_client(mut stream: TcpStream) -> Result<()> {
                 );
                 writer.write_all(proof_preview.as_bytes()).await?;
 
-                let constraints = format!(
-                    "\nAge Range: {} - {}\nBMI Range: {:.1} - {:.1}\n",
-                    response.public_inputs.min_age,
-                    response.public_inputs.max_age,
-                    response.public_inputs.min_bmi as f32 / 10.0,
-                    response.public_inputs.max_bmi as f32 / 10.0
-                );
-                writer.write_all(constraints.as_bytes()).await?;
+                writer.write_all(b"\nPolicy bounds:\n").await?;
+                for (name, value) in &response.public_inputs.0 {
+                    let bound = format!("  {} = {}\n", name, value);
+                    writer.write_all(bound.as_bytes()).await?;
+                }
 
                 let json = serde_json::to_string_pretty(&response)?;
-                writer.write_all(b"\nFull JSON Response:\n").await?;
-                writer.write_all(json.as_bytes()).await?;
+                // Compress the payload on the wire when the client negotiated zstd.
+                if caps.zstd {
+                    let compressed = zstd::encode_all(json.as_bytes(), 0)
+                        .context("Failed to zstd-compress proof payload")?;
+                    let encoded = general_purpose::STANDARD.encode(&compressed);
+                    writer
+                        .write_all(b"\nFull JSON Response (zstd+base64):\n")
+                        .await?;
+                    writer.write_all(encoded.as_bytes()).await?;
+                } else {
+                    writer.write_all(b"\nFull JSON Response:\n").await?;
+                    writer.write_all(json.as_bytes()).await?;
+                }
                 writer.write_all(b"\n").await?;
 
-                // Save proof to file
-                let proof_filename = format!("proof_{}.json", chrono::Utc::now().timestamp());
-                fs::write(&proof_filename, json)?;
+                // Save proof to file. With a key configured, encrypt at rest so
+                // public inputs and proof material never hit disk in cleartext.
+                let timestamp = chrono::Utc::now().timestamp();
+                let proof_filename = match encrypt_key {
+                    Some(key) => {
+                        let filename = format!("proof_{}.enc", timestamp);
+                        let sealed = encrypt_proof(key, json.as_bytes())?;
+                        fs::write(&filename, sealed)?;
+                        filename
+                    }
+                    None => {
+                        let filename = format!("proof_{}.json", timestamp);
+                        fs::write(&filename, &json)?;
+                        filename
+                    }
+                };
                 let save_msg = format!("Proof saved to: {}\n", proof_filename);
                 writer.write_all(save_msg.as_bytes()).await?;
             }
@@ -249,33 +717,532 @@ async fn handle_client(mut stream: TcpStream) -> Result<()> {
         }
     }
 
-    writer.write_all(b"\nConnection will close. Thanks for using ZK Insurance Verifier!\n").await?;
+    Ok(())
+}
+
+async fn handle_client<S>(
+    stream: S,
+    sessions: SessionStore,
+    circuit: Circuit,
+    eth: Option<EthConfig>,
+    encrypt_key: Option<[u8; 32]>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+
+    // Opening handshake: the client either starts a new session with
+    // `HELLO <version> [caps...]` or resumes an existing one with
+    // `RESUME <session-id>`. Anything else falls back to a capability-less
+    // session so plain `nc` clients still work.
+    writer.write_all(b"ZK Insurance Verifier Server\n").await?;
+    writer.write_all(b"============================\n").await?;
+    writer
+        .write_all(b"Handshake: send 'HELLO <version> [zstd] [keepalive]' or 'RESUME <session-id>'\n")
+        .await?;
+    writer.flush().await?;
+
+    reader.read_line(&mut line).await?;
+
+    // Evict expired sessions before handling the handshake so a resume against a
+    // stale ID is correctly rejected and the store stays bounded.
+    let now = chrono::Utc::now().timestamp();
+    sweep_sessions(&sessions, now).await;
+
+    let first = line.trim().to_string();
+    let mut tokens = first.split_whitespace();
+
+    let mut resumed = false;
+    let mut caps = Capabilities::default();
+    let mut session_circuit = circuit;
+    let mut session_id = String::new();
+
+    match tokens.next() {
+        Some("RESUME") => {
+            let id = tokens.next().unwrap_or_default().to_string();
+            // The sweep above already dropped expired sessions, so a hit here is
+            // still within its TTL. Refresh `last_seen` to keep it alive.
+            let existing = {
+                let mut store = sessions.lock().await;
+                if let Some(session) = store.get_mut(&id) {
+                    session.last_seen = now;
+                    Some(session.clone())
+                } else {
+                    None
+                }
+            };
+            match existing {
+                Some(session) => {
+                    session_circuit = session.circuit;
+                    caps = session.caps;
+                    session_id = id;
+                    resumed = true;
+                    let msg = format!("RESUMED {}\n", session_id);
+                    writer.write_all(msg.as_bytes()).await?;
+                }
+                None => {
+                    writer
+                        .write_all(b"Unknown or expired session; starting a new one\n")
+                        .await?;
+                }
+            }
+        }
+        Some("HELLO") => {
+            let _version = tokens.next();
+            caps = Capabilities::negotiate(tokens.by_ref());
+        }
+        _ => {}
+    }
+
+    if !resumed {
+        session_id = new_session_id();
+        let banner = format!(
+            "SESSION {}\nNegotiated: zstd={} keepalive={}\n",
+            session_id, caps.zstd, caps.keepalive
+        );
+        writer.write_all(banner.as_bytes()).await?;
+        sessions.lock().await.insert(
+            session_id.clone(),
+            Session {
+                circuit: session_circuit.clone(),
+                caps: caps.clone(),
+                last_seen: now,
+            },
+        );
+    }
+    writer.flush().await?;
+
+    let circuit_name = session_circuit.name.clone();
+    let prover = NoirProver::new(session_circuit);
+
+    // Serve at least one proof; with keepalive, keep going until the client
+    // declines. The session stays registered so a dropped connection can resume.
+    loop {
+        run_proof_exchange(
+            &mut writer,
+            &mut reader,
+            &prover,
+            &circuit_name,
+            &caps,
+            &eth,
+            &encrypt_key,
+        )
+        .await?;
+
+        if !caps.keepalive {
+            break;
+        }
+
+        writer
+            .write_all(b"\nRun another proof on this session? (y/n): ")
+            .await?;
+        writer.flush().await?;
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let answer = line.trim().to_ascii_lowercase();
+        if answer != "y" && answer != "yes" {
+            break;
+        }
+    }
+
+    // Touch the session on disconnect so its TTL is measured from last use. It is
+    // (re)inserted, since another connection's sweep may have dropped it while
+    // this connection was active.
+    sessions.lock().await.insert(
+        session_id.clone(),
+        Session {
+            circuit: prover.circuit.clone(),
+            caps: caps.clone(),
+            last_seen: chrono::Utc::now().timestamp(),
+        },
+    );
+
+    let closing = format!(
+        "\nConnection will close. Resume this session with: RESUME {} (valid for {}s)\n",
+        session_id, SESSION_TTL_SECS
+    );
+    writer.write_all(closing.as_bytes()).await?;
     writer.flush().await?;
 
     Ok(())
 }
 
+/// Body of a `POST /verify` request: a previously issued proof and its key.
+#[derive(Debug, Deserialize)]
+struct VerifyRequest {
+    proof: String,
+    verification_key: String,
+}
+
+/// Response of `POST /verify`.
+#[derive(Debug, Serialize)]
+struct VerifyResponse {
+    verified: bool,
+}
+
+/// Serialize `value` as a JSON response with the given status code.
+fn json_response<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    let body = serde_json::to_vec(value).unwrap_or_else(|_| b"{}".to_vec());
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// Plain-text error response carrying a status code.
+fn error_response(status: StatusCode, message: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "text/plain")
+        .body(Body::from(message.to_string()))
+        .unwrap()
+}
+
+/// Validate client-supplied inputs against a circuit's declared schema, so the
+/// HTTP layer can return a 4xx for a client mistake instead of letting a missing
+/// or ill-typed input surface as a 500 from deep inside proof generation.
+/// Returns a human-readable reason on the first violation.
+fn validate_inputs(
+    config: &CircuitConfig,
+    inputs: &HashMap<String, serde_json::Value>,
+) -> std::result::Result<(), String> {
+    for name in &config.private_inputs {
+        match inputs.get(name) {
+            None => return Err(format!("Missing required input '{}'", name)),
+            Some(value) if !(value.is_string() || value.is_u64() || value.is_i64()) => {
+                return Err(format!("Input '{}' must be a string or integer", name));
+            }
+            Some(_) => {}
+        }
+    }
+    for key in inputs.keys() {
+        if !config.private_inputs.contains(key) {
+            return Err(format!("Unknown input '{}'", key));
+        }
+    }
+    Ok(())
+}
+
+/// Record a successful proof on-chain when an [`EthConfig`] is configured,
+/// folding the transaction hash and verdict into the response.
+async fn record_on_chain(response: &mut ProofResponse, eth: &Option<EthConfig>) -> Result<()> {
+    if response.success {
+        if let Some(eth) = eth {
+            let (tx_hash, verified) =
+                submit_on_chain(eth, &response.proof, &response.public_inputs).await?;
+            response.tx_hash = Some(tx_hash);
+            response.on_chain_verified = Some(verified);
+        }
+    }
+    Ok(())
+}
+
+/// Persist a proof's JSON to the working directory, encrypting it at rest when a
+/// key is configured. Returns the filename written.
+fn persist_proof(json: &str, encrypt_key: &Option<[u8; 32]>) -> Result<String> {
+    let timestamp = chrono::Utc::now().timestamp();
+    match encrypt_key {
+        Some(key) => {
+            let filename = format!("proof_{}.enc", timestamp);
+            fs::write(&filename, encrypt_proof(key, json.as_bytes())?)?;
+            Ok(filename)
+        }
+        None => {
+            let filename = format!("proof_{}.json", timestamp);
+            fs::write(&filename, json)?;
+            Ok(filename)
+        }
+    }
+}
+
+/// Route a single HTTP request. Unparseable input is `400`, inputs that violate
+/// the circuit schema or constraints are `422`, and a generated proof is `200`.
+/// The same on-chain recording and at-rest encryption options as the TCP
+/// transport are honoured.
+async fn handle_http(
+    req: Request<Body>,
+    registry: Arc<CircuitRegistry>,
+    eth: Arc<Option<EthConfig>>,
+    encrypt_key: Option<[u8; 32]>,
+) -> Response<Body> {
+    match (req.method(), req.uri().path()) {
+        (&Method::POST, "/prove") => {
+            let bytes = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(b) => b,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            };
+            let request: ProofRequest = match serde_json::from_slice(&bytes) {
+                Ok(r) => r,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            };
+            let circuit = match registry.get(&request.circuit) {
+                Some(c) => c,
+                None => {
+                    return error_response(
+                        StatusCode::BAD_REQUEST,
+                        &format!("Unknown circuit '{}'", request.circuit),
+                    );
+                }
+            };
+            // Client-caused schema violations are 422, not 500.
+            if let Err(reason) = validate_inputs(&circuit.config, &request.inputs) {
+                return error_response(StatusCode::UNPROCESSABLE_ENTITY, &reason);
+            }
+            match NoirProver::new(circuit).generate_proof(request).await {
+                Ok(mut response) => {
+                    // On-chain recording is best-effort; a failure there leaves the
+                    // proof intact but unrecorded rather than failing the request.
+                    if let Err(e) = record_on_chain(&mut response, &eth).await {
+                        eprintln!("On-chain submission failed: {}", e);
+                    }
+                    if response.success {
+                        let json = match serde_json::to_string(&response) {
+                            Ok(j) => j,
+                            Err(e) => {
+                                return error_response(
+                                    StatusCode::INTERNAL_SERVER_ERROR,
+                                    &e.to_string(),
+                                );
+                            }
+                        };
+                        if let Err(e) = persist_proof(&json, &encrypt_key) {
+                            return error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string());
+                        }
+                    }
+                    let status = if response.success {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::UNPROCESSABLE_ENTITY
+                    };
+                    json_response(status, &response)
+                }
+                Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            }
+        }
+        (&Method::POST, "/verify") => {
+            let bytes = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(b) => b,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            };
+            let request: VerifyRequest = match serde_json::from_slice(&bytes) {
+                Ok(r) => r,
+                Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+            };
+            match NoirProver::verify_proof(&request.proof, &request.verification_key).await {
+                Ok(verified) => json_response(StatusCode::OK, &VerifyResponse { verified }),
+                Err(e) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+            }
+        }
+        _ => error_response(StatusCode::NOT_FOUND, "Not found"),
+    }
+}
+
+/// Run the JSON HTTP API on `addr`, sharing the circuit registry and the
+/// on-chain / at-rest-encryption options across requests.
+async fn serve_http(
+    addr: std::net::SocketAddr,
+    registry: Arc<CircuitRegistry>,
+    eth: Option<EthConfig>,
+    encrypt_key: Option<[u8; 32]>,
+) -> Result<()> {
+    let eth = Arc::new(eth);
+    let make_svc = make_service_fn(move |_conn| {
+        let registry = registry.clone();
+        let eth = eth.clone();
+        async move {
+            Ok::<_, std::convert::Infallible>(service_fn(move |req| {
+                let registry = registry.clone();
+                let eth = eth.clone();
+                async move {
+                    Ok::<_, std::convert::Infallible>(
+                        handle_http(req, registry, eth, encrypt_key).await,
+                    )
+                }
+            }))
+        }
+    });
+
+    Server::bind(&addr)
+        .serve(make_svc)
+        .await
+        .context("HTTP server error")?;
+    Ok(())
+}
+
+/// Decode a hex-encoded 32-byte AEAD key.
+fn parse_encrypt_key(hex_key: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(hex_key.trim()).context("--encrypt-key is not valid hex")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("--encrypt-key must be exactly 32 bytes (64 hex chars)"))
+}
+
+/// Encrypt `plaintext` with ChaCha20-Poly1305 under a fresh random nonce,
+/// returning `nonce || ciphertext`.
+fn encrypt_proof(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| anyhow::anyhow!("Failed to encrypt proof: {}", e))?;
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Reverse [`encrypt_proof`]: split off the 12-byte nonce and decrypt the rest.
+fn decrypt_proof(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < 12 {
+        anyhow::bail!("Encrypted proof is too short to contain a nonce");
+    }
+    let (nonce_bytes, ciphertext) = data.split_at(12);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = Nonce::from_slice(nonce_bytes);
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| anyhow::anyhow!("Failed to decrypt proof: {}", e))
+}
+
+/// Build a `rustls::ServerConfig` from PEM-encoded certificate and key files.
+fn load_tls_config(cert_path: &str, key_path: &str) -> Result<ServerConfig> {
+    let cert_pem = fs::read(cert_path)
+        .with_context(|| format!("Failed to read TLS certificate: {}", cert_path))?;
+    let key_pem =
+        fs::read(key_path).with_context(|| format!("Failed to read TLS key: {}", key_path))?;
+
+    let certs = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .context("Failed to parse TLS certificate chain")?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+
+    let mut keys = rustls_pemfile::pkcs8_private_keys(&mut key_pem.as_slice())
+        .context("Failed to parse TLS private key")?;
+    let key = keys
+        .pop()
+        .context("No PKCS#8 private key found in TLS key file")?;
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, PrivateKey(key))
+        .context("Failed to build TLS server config")?;
+
+    Ok(config)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+
+    // Parse the optional at-rest encryption key up front so a bad key fails fast.
+    let encrypt_key = match &args.encrypt_key {
+        Some(hex_key) => Some(parse_encrypt_key(hex_key)?),
+        None => None,
+    };
+
+    // `--decrypt <file>` is a standalone utility: recover a stored proof to
+    // stdout and exit without starting the server.
+    if let Some(path) = &args.decrypt {
+        let key = encrypt_key.context("--decrypt requires --encrypt-key")?;
+        let data = fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+        let plaintext = decrypt_proof(&key, &data)?;
+        println!("{}", String::from_utf8_lossy(&plaintext));
+        return Ok(());
+    }
+
     let addr = format!("0.0.0.0:{}", args.port);
-    
+
+    // Fail fast if TLS is requested without the cert/key material.
+    let tls_acceptor = if args.tls {
+        let cert = args
+            .tls_cert
+            .as_deref()
+            .context("--tls requires --tls-cert")?;
+        let key = args.tls_key.as_deref().context("--tls requires --tls-key")?;
+        let config = load_tls_config(cert, key)?;
+        Some(TlsAcceptor::from(Arc::new(config)))
+    } else {
+        None
+    };
+
     println!("ZK Insurance Verifier TCP Server");
     println!("================================");
-    println!("Listening on {}", addr);
+    println!("Listening on {} ({})", addr, if tls_acceptor.is_some() && !args.http { "TLS" } else { "plaintext" });
     println!("Connect using: nc 127.0.0.1 {}", args.port);
     println!("Or: telnet 127.0.0.1 {}", args.port);
     println!();
 
+    // Resolve the optional on-chain verifier configuration once at startup.
+    // Recording the proof is a signed transaction, so a private key is required
+    // alongside the RPC endpoint and verifier address; fail fast if one is missing.
+    let eth = match (&args.eth_rpc, &args.verifier_address) {
+        (Some(rpc), Some(address)) => {
+            let private_key = args
+                .eth_private_key
+                .clone()
+                .context("--eth-rpc/--verifier-address require --eth-private-key to sign the recording transaction")?;
+            Some(EthConfig {
+                rpc: rpc.clone(),
+                verifier_address: address.clone(),
+                private_key,
+            })
+        }
+        _ => None,
+    };
+
+    // Load the circuit registry. Default to the Docker layout (/app/circuits)
+    // when present, otherwise the local checkout (../circuits).
+    let circuits_dir = match &args.circuits_dir {
+        Some(dir) => PathBuf::from(dir),
+        None if Path::new("/app/circuits").exists() => PathBuf::from("/app/circuits"),
+        None => PathBuf::from("../circuits"),
+    };
+    let registry = CircuitRegistry::load(&circuits_dir)?;
+    let default_circuit = registry
+        .get(&args.circuit)
+        .with_context(|| format!("Circuit '{}' not found in {}", args.circuit, circuits_dir.display()))?;
+
+    // HTTP mode serves the same proving core over a JSON API instead of the
+    // line-based TCP protocol.
+    if args.http {
+        let socket_addr: std::net::SocketAddr = addr.parse().context("Invalid listen address")?;
+        println!("Serving JSON HTTP API on http://{}", socket_addr);
+        println!("  POST /prove   -> ProofResponse");
+        println!("  POST /verify  -> {{ \"verified\": bool }}");
+        return serve_http(socket_addr, Arc::new(registry), eth, encrypt_key).await;
+    }
+
+    let sessions: SessionStore = Arc::new(Mutex::new(HashMap::new()));
     let listener = TcpListener::bind(&addr).await?;
 
     loop {
         match listener.accept().await {
             Ok((stream, addr)) => {
                 println!("New connection from: {}", addr);
-                
+
+                let tls_acceptor = tls_acceptor.clone();
+                let eth = eth.clone();
+                let circuit = default_circuit.clone();
+                let sessions = sessions.clone();
                 tokio::spawn(async move {
-                    if let Err(e) = handle_client(stream).await {
+                    let result = match tls_acceptor {
+                        Some(acceptor) => match acceptor.accept(stream).await {
+                            Ok(tls_stream) => {
+                                handle_client(tls_stream, sessions, circuit, eth, encrypt_key).await
+                            }
+                            Err(e) => Err(anyhow::Error::new(e).context("TLS handshake failed")),
+                        },
+                        None => handle_client(stream, sessions, circuit, eth, encrypt_key).await,
+                    };
+
+                    if let Err(e) = result {
                         eprintln!("Error handling client {}: {}", addr, e);
                     } else {
                         println!("Client {} disconnected", addr);
@@ -287,4 +1254,76 @@ async fn main() -> Result<()> {
             }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pi(name: &str, value: i64) -> PublicInput {
+        PublicInput {
+            name: name.to_string(),
+            value: toml::Value::Integer(value),
+        }
+    }
+
+    #[test]
+    fn encrypt_decrypt_round_trips() {
+        let key = [7u8; 32];
+        let plaintext = b"{\"success\":true}";
+        let sealed = encrypt_proof(&key, plaintext).unwrap();
+        // nonce || ciphertext, never the cleartext itself.
+        assert_ne!(&sealed[12..], &plaintext[..]);
+        let opened = decrypt_proof(&key, &sealed).unwrap();
+        assert_eq!(opened, plaintext);
+    }
+
+    #[test]
+    fn decrypt_rejects_tampered_ciphertext() {
+        let key = [7u8; 32];
+        let mut sealed = encrypt_proof(&key, b"payload").unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 0x01;
+        assert!(decrypt_proof(&key, &sealed).is_err());
+    }
+
+    #[test]
+    fn negotiate_intersects_offered_tokens() {
+        let caps = Capabilities::negotiate(["zstd", "bogus"]);
+        assert!(caps.zstd);
+        assert!(!caps.keepalive);
+
+        let both = Capabilities::negotiate(["keepalive", "zstd"]);
+        assert!(both.zstd);
+        assert!(both.keepalive);
+
+        assert!(!Capabilities::negotiate([]).zstd);
+    }
+
+    #[test]
+    fn policy_public_inputs_preserves_declared_order() {
+        let bounds = [pi("min_age", 10), pi("max_age", 25), pi("min_bmi", 185)];
+        let public = policy_public_inputs(&bounds);
+        let names: Vec<&str> = public.0.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["min_age", "max_age", "min_bmi"]);
+    }
+
+    #[test]
+    fn public_inputs_serialize_in_insertion_order() {
+        let public = PublicInputs(vec![
+            ("min_age".to_string(), serde_json::Value::from(10)),
+            ("max_age".to_string(), serde_json::Value::from(25)),
+            ("min_bmi".to_string(), serde_json::Value::from(185)),
+        ]);
+        let json = serde_json::to_string(&public).unwrap();
+        // Keys must appear in Vec order, not sorted alphabetically.
+        assert_eq!(json, r#"{"min_age":10,"max_age":25,"min_bmi":185}"#);
+    }
+
+    #[test]
+    fn parse_encrypt_key_enforces_length() {
+        assert!(parse_encrypt_key(&"ab".repeat(32)).is_ok());
+        assert!(parse_encrypt_key("dead").is_err());
+        assert!(parse_encrypt_key("nothex").is_err());
+    }
 }
\ No newline at end of file